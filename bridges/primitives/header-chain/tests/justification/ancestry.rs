@@ -0,0 +1,74 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests asserting that `votes_ancestries` must actually connect every precommit target to the
+//! justification target.
+
+use bp_header_chain::justification::{verify_justification, JustificationVerificationError};
+use bp_test_utils::*;
+use sp_runtime::traits::Header as HeaderT;
+
+type TestHeader = sp_runtime::testing::Header;
+
+#[test]
+fn rejects_precommit_target_with_no_ancestry_at_all() {
+	let mut justification = make_default_justification::<TestHeader>(&test_header(1));
+	// Point a precommit at a block that is neither the target nor present in the ancestry. The
+	// precommit is re-signed, so that it fails on the ancestry check and not on its signature.
+	let unconnected = test_header::<TestHeader>(42);
+	justification.commit.precommits[0] = signed_precommit::<TestHeader>(
+		&ALICE,
+		(unconnected.hash(), *unconnected.number()),
+		justification.round,
+		TEST_GRANDPA_SET_ID,
+	);
+
+	assert_eq!(
+		verify_justification::<TestHeader>(
+			header_id::<TestHeader>(1),
+			&verification_context(TEST_GRANDPA_SET_ID),
+			&justification,
+		),
+		Err(JustificationVerificationError::UnconnectedPrecommitTarget),
+	);
+}
+
+#[test]
+fn rejects_ancestry_that_does_not_reach_the_target() {
+	let mut justification = make_default_justification::<TestHeader>(&test_header(1));
+	// Replace the ancestry with a header whose parent is unknown, so following it from a
+	// precommit's target never reaches the justification target. The precommit is re-signed, so
+	// that it fails on the ancestry check and not on its signature.
+	let mut dangling = justification.votes_ancestries[0].clone();
+	dangling.parent_hash = test_header::<TestHeader>(99).hash();
+	let dangling_id = (dangling.hash(), *dangling.number());
+	justification.votes_ancestries = vec![dangling];
+	justification.commit.precommits[0] = signed_precommit::<TestHeader>(
+		&ALICE,
+		dangling_id,
+		justification.round,
+		TEST_GRANDPA_SET_ID,
+	);
+
+	assert_eq!(
+		verify_justification::<TestHeader>(
+			header_id::<TestHeader>(1),
+			&verification_context(TEST_GRANDPA_SET_ID),
+			&justification,
+		),
+		Err(JustificationVerificationError::BrokenAncestryChain),
+	);
+}
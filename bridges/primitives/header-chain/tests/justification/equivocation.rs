@@ -0,0 +1,107 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests for `bp_header_chain::justification::extract_equivocations`.
+
+use bp_header_chain::justification::{
+	extract_equivocations, verify_justification, JustificationVerificationError,
+};
+use bp_test_utils::*;
+use sp_consensus_grandpa::AuthorityId;
+use sp_runtime::traits::Header as HeaderT;
+
+type TestHeader = sp_runtime::testing::Header;
+
+#[test]
+fn no_equivocations_in_a_valid_justification() {
+	let justification = make_default_justification::<TestHeader>(&test_header(1));
+
+	assert!(extract_equivocations::<TestHeader>(
+		&verification_context(TEST_GRANDPA_SET_ID),
+		&justification,
+	)
+	.is_empty());
+}
+
+#[test]
+fn equivocation_is_detected_and_reported() {
+	let authorities = vec![(ALICE, 1), (BOB, 1), (CHARLIE, 1)];
+
+	// Two justifications for two different headers, signed by the same authority set in the
+	// same round and authority set - ALICE's vote on `second` is a genuine, validly-signed
+	// equivocation once merged into `first`'s precommits.
+	let first = make_justification_for_header::<TestHeader>(JustificationGeneratorParams {
+		header: test_header(1),
+		round: TEST_GRANDPA_ROUND,
+		set_id: TEST_GRANDPA_SET_ID,
+		authorities: authorities.clone(),
+		ancestors: 1,
+		forks: 1,
+	});
+	let second = make_justification_for_header::<TestHeader>(JustificationGeneratorParams {
+		header: test_header(2),
+		round: TEST_GRANDPA_ROUND,
+		set_id: TEST_GRANDPA_SET_ID,
+		authorities,
+		ancestors: 1,
+		forks: 1,
+	});
+
+	let alice = AuthorityId::from(ALICE);
+	let alice_vote_on_second = second
+		.commit
+		.precommits
+		.into_iter()
+		.find(|signed| signed.id == alice)
+		.expect("ALICE is in the voter set and signed a precommit");
+
+	let mut justification = first;
+	justification.commit.precommits.push(alice_vote_on_second);
+
+	let context = verification_context(TEST_GRANDPA_SET_ID);
+	let equivocations = extract_equivocations::<TestHeader>(&context, &justification);
+
+	assert_eq!(equivocations.len(), 1);
+	assert_eq!(equivocations[0].offender, alice);
+	assert_eq!(equivocations[0].round, justification.round);
+	assert_eq!(equivocations[0].set_id, TEST_GRANDPA_SET_ID);
+
+	assert_eq!(
+		verify_justification::<TestHeader>(
+			header_id::<TestHeader>(1),
+			&context,
+			&justification,
+		),
+		Err(JustificationVerificationError::Equivocation(alice)),
+	);
+}
+
+#[test]
+fn forged_precommit_with_mismatched_signature_is_not_reported_as_equivocation() {
+	let mut justification = make_default_justification::<TestHeader>(&test_header(1));
+	// Reuses a real, validly-signed precommit's signature for a different target, without
+	// re-signing it - the signature no longer matches the claimed vote.
+	let mut forged_precommit = justification.commit.precommits[0].clone();
+	forged_precommit.precommit.target_hash = test_header::<TestHeader>(2).hash();
+	justification.commit.precommits.push(forged_precommit);
+
+	let equivocations = extract_equivocations::<TestHeader>(
+		&verification_context(TEST_GRANDPA_SET_ID),
+		&justification,
+	);
+
+	assert!(equivocations.is_empty());
+}
@@ -0,0 +1,122 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests for `bp_header_chain::justification::verify_justification_with_info`.
+
+use bp_header_chain::justification::{
+	optimize_justification, verify_justification_with_info, JustificationVerificationContext,
+};
+use bp_test_utils::*;
+use finality_grandpa::voter_set::VoterSet;
+use sp_consensus_grandpa::AuthorityId;
+
+type TestHeader = sp_runtime::testing::Header;
+
+#[test]
+fn reports_accurate_info_for_a_minimal_justification() {
+	let authorities = vec![(ALICE, 1), (BOB, 1), (CHARLIE, 1)];
+	let params = JustificationGeneratorParams {
+		header: test_header(1),
+		round: TEST_GRANDPA_ROUND,
+		set_id: TEST_GRANDPA_SET_ID,
+		authorities: authorities.clone(),
+		ancestors: 7,
+		forks: 3,
+	};
+
+	let justification = make_justification_for_header::<TestHeader>(params);
+	let context = verification_context(TEST_GRANDPA_SET_ID);
+	let optimized =
+		optimize_justification::<TestHeader>(header_id::<TestHeader>(1), &context, &justification)
+			.unwrap();
+
+	let info =
+		verify_justification_with_info::<TestHeader>(header_id::<TestHeader>(1), &context, &optimized)
+			.unwrap();
+
+	assert_eq!(info.authorities.len(), authorities.len());
+	assert_eq!(info.num_precommits, optimized.commit.precommits.len());
+	assert_eq!(info.num_ancestry_headers, optimized.votes_ancestries.len());
+	assert!(info.cumulative_weight >= info.threshold);
+	assert!(info.is_optimal());
+}
+
+#[test]
+fn flags_a_non_minimized_justification_as_non_optimal() {
+	let authorities = accounts(4).iter().map(|k| (*k, 1)).collect::<Vec<_>>();
+	let params = JustificationGeneratorParams {
+		header: test_header(1),
+		round: TEST_GRANDPA_ROUND,
+		set_id: TEST_GRANDPA_SET_ID,
+		authorities: authorities.clone(),
+		ancestors: 7,
+		forks: 3,
+	};
+
+	let justification = make_justification_for_header::<TestHeader>(params);
+	let voter_set = VoterSet::new(
+		authorities.iter().map(|(id, weight)| (AuthorityId::from(*id), *weight)),
+	)
+	.unwrap();
+	let context =
+		JustificationVerificationContext { voter_set, authority_set_id: TEST_GRANDPA_SET_ID };
+
+	let info = verify_justification_with_info::<TestHeader>(
+		header_id::<TestHeader>(1),
+		&context,
+		&justification,
+	)
+	.unwrap();
+
+	// All four equal-weight authorities signed, but only three of them are required to reach the
+	// threshold (4 - (4 - 1) / 3 = 3).
+	assert!(!info.is_optimal());
+}
+
+#[test]
+fn flags_unequal_weight_justification_as_non_optimal() {
+	// Total weight is 5, so the threshold is 4. Every authority signs, but the weight-3 vote
+	// alone isn't enough to tell whether a vote could be dropped - the smallest (weight-1) vote
+	// is the one that has to be checked, and dropping it still leaves 4 >= 4.
+	let authorities = vec![(ALICE, 3), (BOB, 1), (CHARLIE, 1)];
+	let params = JustificationGeneratorParams {
+		header: test_header(1),
+		round: TEST_GRANDPA_ROUND,
+		set_id: TEST_GRANDPA_SET_ID,
+		authorities: authorities.clone(),
+		ancestors: 7,
+		forks: 3,
+	};
+
+	let justification = make_justification_for_header::<TestHeader>(params);
+	// `verification_context` gives every authority a weight of 1, so the voter set is built from
+	// `authorities` to carry the weights above.
+	let voter_set = VoterSet::new(
+		authorities.iter().map(|(id, weight)| (AuthorityId::from(*id), *weight)),
+	)
+	.unwrap();
+	let context =
+		JustificationVerificationContext { voter_set, authority_set_id: TEST_GRANDPA_SET_ID };
+
+	let info = verify_justification_with_info::<TestHeader>(
+		header_id::<TestHeader>(1),
+		&context,
+		&justification,
+	)
+	.unwrap();
+
+	assert!(!info.is_optimal());
+}
@@ -0,0 +1,96 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests for `bp_header_chain::justification::optimize_justification`.
+
+use bp_header_chain::justification::{optimize_justification, verify_justification};
+use bp_test_utils::*;
+
+type TestHeader = sp_runtime::testing::Header;
+
+#[test]
+fn optimized_justification_is_still_valid() {
+	let params = JustificationGeneratorParams {
+		header: test_header(1),
+		round: TEST_GRANDPA_ROUND,
+		set_id: TEST_GRANDPA_SET_ID,
+		authorities: vec![(ALICE, 1), (BOB, 1), (CHARLIE, 1)],
+		ancestors: 7,
+		forks: 3,
+	};
+
+	let justification = make_justification_for_header::<TestHeader>(params);
+	let context = verification_context(TEST_GRANDPA_SET_ID);
+	let optimized =
+		optimize_justification::<TestHeader>(header_id::<TestHeader>(1), &context, &justification)
+			.unwrap();
+
+	assert_eq!(
+		verify_justification::<TestHeader>(header_id::<TestHeader>(1), &context, &optimized),
+		Ok(()),
+	);
+}
+
+#[test]
+fn optimized_justification_is_not_larger_than_original() {
+	let params = JustificationGeneratorParams {
+		header: test_header(1),
+		round: TEST_GRANDPA_ROUND,
+		set_id: TEST_GRANDPA_SET_ID,
+		authorities: vec![(ALICE, 1), (BOB, 1), (CHARLIE, 1)],
+		ancestors: 7,
+		forks: 3,
+	};
+
+	let justification = make_justification_for_header::<TestHeader>(params);
+	let context = verification_context(TEST_GRANDPA_SET_ID);
+	let optimized =
+		optimize_justification::<TestHeader>(header_id::<TestHeader>(1), &context, &justification)
+			.unwrap();
+
+	assert!(optimized.commit.precommits.len() <= justification.commit.precommits.len());
+	assert!(optimized.votes_ancestries.len() <= justification.votes_ancestries.len());
+}
+
+#[test]
+fn optimized_justification_drops_redundant_and_duplicate_ancestries() {
+	let mut justification = make_default_justification::<TestHeader>(&test_header(1));
+	justification.votes_ancestries.push(test_header(10));
+	justification.votes_ancestries.push(justification.votes_ancestries[0].clone());
+
+	let context = verification_context(TEST_GRANDPA_SET_ID);
+	let optimized =
+		optimize_justification::<TestHeader>(header_id::<TestHeader>(1), &context, &justification)
+			.unwrap();
+
+	assert_eq!(
+		verify_justification::<TestHeader>(header_id::<TestHeader>(1), &context, &optimized),
+		Ok(()),
+	);
+}
+
+#[test]
+fn optimize_justification_rejects_invalid_target() {
+	assert_eq!(
+		optimize_justification::<TestHeader>(
+			header_id::<TestHeader>(2),
+			&verification_context(TEST_GRANDPA_SET_ID),
+			&make_default_justification::<TestHeader>(&test_header(1)),
+		)
+		.map(drop),
+		Err(bp_header_chain::justification::JustificationVerificationError::InvalidJustificationTarget),
+	);
+}